@@ -0,0 +1,264 @@
+// Copyright 2016 Mozilla Foundation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+
+use crate::cache::eviction::{Admission, EvictionPolicy};
+use crate::cache::{Cache, CacheMode, CacheRead, CacheWrite, Storage};
+use crate::errors::*;
+
+/// How many existing entries to sample as eviction candidates when the
+/// cache is full. Sampling a handful rather than every entry keeps `put`
+/// cheap even with a large number of cached files.
+const SAMPLE_SIZE: usize = 5;
+
+/// Recursively sum the size on disk of everything under `root`, so a
+/// freshly-constructed `DiskCache` starts its running size counter from
+/// whatever a previous process already left behind. This walk only
+/// happens once, at construction; `put` maintains `current_size`
+/// incrementally afterwards rather than re-stating the directory.
+fn scan_size(root: &Path) -> u64 {
+    let mut total = 0u64;
+    let entries = match fs::read_dir(root) {
+        Ok(entries) => entries,
+        Err(_) => return 0,
+    };
+    for entry in entries.flatten() {
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => total += scan_size(&entry.path()),
+            Ok(_) => total += entry.metadata().map(|m| m.len()).unwrap_or(0),
+            Err(_) => {}
+        }
+    }
+    total
+}
+
+/// A local, filesystem-backed `Storage` bounded by `max_size` bytes.
+///
+/// Admission when the cache is full, and any periodic maintenance a
+/// policy needs, are delegated to a pluggable `EvictionPolicy` (see
+/// `eviction::tiny_lfu::TinyLfuPolicy` and `eviction::clock::ClockPolicy`)
+/// rather than hard-coded here. `put` tracks the cache's total size with
+/// an in-memory running counter rather than re-stating every cached file
+/// on each write, so it stays cheap regardless of how many entries are
+/// cached; the counter is seeded once at construction from whatever is
+/// already on disk.
+pub struct DiskCache {
+    root: PathBuf,
+    max_size: Option<u64>,
+    policy: Mutex<Box<dyn EvictionPolicy>>,
+    current_size: AtomicU64,
+}
+
+impl DiskCache {
+    /// Create a cache rooted at `root`, bounded by `max_size` bytes (if
+    /// set) and governed by `policy`.
+    pub fn new(root: impl Into<PathBuf>, max_size: Option<u64>, policy: Box<dyn EvictionPolicy>) -> DiskCache {
+        let root = root.into();
+        let current_size = scan_size(&root);
+        DiskCache {
+            root,
+            max_size,
+            policy: Mutex::new(policy),
+            current_size: AtomicU64::new(current_size),
+        }
+    }
+
+    fn path_for(&self, key: &str) -> PathBuf {
+        self.root.join(key)
+    }
+
+    /// Remove the cache entry for `key`, if present, and reflect its size
+    /// in the running `current_size` counter.
+    fn evict(&self, key: &str) {
+        let path = self.path_for(key);
+        if let Ok(metadata) = fs::metadata(&path) {
+            if fs::remove_file(&path).is_ok() {
+                self.current_size.fetch_sub(metadata.len(), Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// A bounded sample of keys currently on disk, to offer an
+    /// `EvictionPolicy` as eviction candidates.
+    fn sample_existing_keys(&self) -> Result<Vec<String>> {
+        let entries = match fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).chain_err(|| "failed to read cache directory"),
+        };
+        let mut keys = Vec::new();
+        for entry in entries {
+            if keys.len() >= SAMPLE_SIZE {
+                break;
+            }
+            let entry = entry.chain_err(|| "failed to read cache directory entry")?;
+            if let Some(name) = entry.file_name().to_str() {
+                keys.push(name.to_owned());
+            }
+        }
+        Ok(keys)
+    }
+}
+
+#[async_trait]
+impl Storage for DiskCache {
+    async fn get(&self, key: &str) -> Result<Cache> {
+        match fs::read(self.path_for(key)) {
+            Ok(data) => {
+                self.policy.lock().await.record_access(key);
+                Ok(Cache::Hit(CacheRead::from_bytes(data)))
+            }
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Cache::Miss),
+            Err(e) => Err(e).chain_err(|| "failed to read cache entry"),
+        }
+    }
+
+    async fn put(&self, key: &str, entry: CacheWrite) -> Result<Duration> {
+        let start = Instant::now();
+
+        if let Some(max_size) = self.max_size {
+            if self.current_size.load(Ordering::Relaxed) >= max_size {
+                let candidates = self.sample_existing_keys()?;
+                let candidate_refs: Vec<&str> = candidates.iter().map(String::as_str).collect();
+                match self.policy.lock().await.admit(key, &candidate_refs) {
+                    Admission::Reject => {
+                        error_chain::bail!("cache is at capacity and rejected `{}`", key)
+                    }
+                    Admission::Admit(Some(victim)) => self.evict(&victim),
+                    Admission::Admit(None) => {}
+                }
+            }
+        }
+
+        let path = self.path_for(key);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).chain_err(|| "failed to create cache directory")?;
+        }
+        let previous_len = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        fs::write(&path, entry.bytes()).chain_err(|| "failed to write cache entry")?;
+        self.current_size
+            .fetch_add(entry.bytes().len() as u64, Ordering::Relaxed);
+        if previous_len > 0 {
+            self.current_size.fetch_sub(previous_len, Ordering::Relaxed);
+        }
+
+        let evicted = {
+            let mut policy = self.policy.lock().await;
+            policy.record_access(key);
+            policy.maintain()
+        };
+        for victim in evicted {
+            self.evict(&victim);
+        }
+
+        Ok(start.elapsed())
+    }
+
+    async fn check(&self) -> Result<CacheMode> {
+        Ok(CacheMode::ReadWrite)
+    }
+
+    fn location(&self) -> String {
+        format!("Local disk cache at {}", self.root.display())
+    }
+
+    async fn current_size(&self) -> Result<Option<u64>> {
+        Ok(Some(self.current_size.load(Ordering::Relaxed)))
+    }
+
+    async fn max_size(&self) -> Result<Option<u64>> {
+        Ok(self.max_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::eviction::tiny_lfu::TinyLfuPolicy;
+
+    #[tokio::test]
+    async fn put_then_get_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = DiskCache::new(dir.path(), None, Box::new(TinyLfuPolicy::new(16)));
+
+        storage.put("key", CacheWrite::from_bytes(b"hello".to_vec())).await.unwrap();
+        let Cache::Hit(entry) = storage.get("key").await.unwrap() else {
+            panic!("expected a hit");
+        };
+        assert_eq!(entry.into_bytes(), b"hello");
+    }
+
+    #[tokio::test]
+    async fn missing_key_is_a_miss() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = DiskCache::new(dir.path(), None, Box::new(TinyLfuPolicy::new(16)));
+
+        assert!(matches!(storage.get("missing").await.unwrap(), Cache::Miss));
+    }
+
+    #[tokio::test]
+    async fn current_size_reflects_written_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = DiskCache::new(dir.path(), None, Box::new(TinyLfuPolicy::new(16)));
+
+        storage.put("key", CacheWrite::from_bytes(b"hello".to_vec())).await.unwrap();
+        assert_eq!(storage.current_size().await.unwrap(), Some(5));
+    }
+
+    #[tokio::test]
+    async fn rejects_a_cold_entry_when_at_capacity() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = DiskCache::new(dir.path(), Some(5), Box::new(TinyLfuPolicy::new(16)));
+
+        storage.put("existing", CacheWrite::from_bytes(b"aaaaa".to_vec())).await.unwrap();
+        {
+            let mut policy = storage.policy.lock().await;
+            for _ in 0..10 {
+                policy.record_access("existing");
+            }
+        }
+
+        let result = storage.put("incoming", CacheWrite::from_bytes(b"bbbbb".to_vec())).await;
+        assert!(result.is_err());
+        assert!(!dir.path().join("incoming").exists());
+        assert_eq!(storage.current_size().await.unwrap(), Some(5));
+    }
+
+    #[tokio::test]
+    async fn evicts_the_coldest_candidate_to_admit_a_hotter_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = DiskCache::new(dir.path(), Some(5), Box::new(TinyLfuPolicy::new(16)));
+
+        storage.put("cold", CacheWrite::from_bytes(b"aaaaa".to_vec())).await.unwrap();
+        {
+            let mut policy = storage.policy.lock().await;
+            for _ in 0..10 {
+                policy.record_access("hot");
+            }
+        }
+
+        storage.put("hot", CacheWrite::from_bytes(b"bbbbb".to_vec())).await.unwrap();
+
+        assert!(!dir.path().join("cold").exists());
+        assert!(dir.path().join("hot").exists());
+        assert_eq!(storage.current_size().await.unwrap(), Some(5));
+    }
+}
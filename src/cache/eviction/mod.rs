@@ -0,0 +1,64 @@
+// Copyright 2016 Mozilla Foundation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pluggable admission/eviction policies for the local disk `Storage`.
+//!
+//! The disk cache bounds its size by asking an `EvictionPolicy` which of
+//! the entries it already holds should make way for a new one. Different
+//! policies trade off bookkeeping cost against how well they approximate
+//! "keep what's actually hot".
+
+pub mod clock;
+pub mod tiny_lfu;
+
+/// The result of asking an `EvictionPolicy` whether to admit a new entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Admission {
+    /// Drop the new entry; the sampled candidates are left untouched.
+    Reject,
+    /// Admit the new entry. `Some(key)` names which of the offered
+    /// candidates to evict to make room; `None` means admit without
+    /// evicting anything right now, because the policy only reclaims
+    /// space through its own periodic `maintain` sweep.
+    Admit(Option<String>),
+}
+
+/// A policy that decides which entries to evict from a size-bounded local
+/// cache, and whether a new entry is even worth admitting in the first
+/// place.
+pub trait EvictionPolicy: Send + Sync {
+    /// Record that `key` was read or written.
+    fn record_access(&mut self, key: &str);
+
+    /// The cache is at capacity and wants to store `key`. `candidates` are
+    /// keys of entries the cache is willing to evict to make room. The
+    /// policy picks which, if any, of `candidates` is least valuable and
+    /// should be evicted to admit `key`; it may also reject `key` outright,
+    /// or admit it without naming a candidate to evict (for a policy that
+    /// only reclaims space via `maintain`).
+    fn admit(&mut self, key: &str, candidates: &[&str]) -> Admission;
+
+    /// Called on every write so policies that only need to act
+    /// periodically (not on every write) can decide for themselves when
+    /// enough writes have accumulated to actually do work; the check
+    /// itself should stay cheap (e.g. a counter compare) so calling this
+    /// unconditionally doesn't reintroduce the per-write cost a batched
+    /// policy is trying to avoid.
+    ///
+    /// Returns the keys, if any, that this call evicted, so the caller
+    /// can delete the corresponding cache entries.
+    fn maintain(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+}
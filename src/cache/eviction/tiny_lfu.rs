@@ -0,0 +1,284 @@
+// Copyright 2016 Mozilla Foundation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::cache::eviction::{Admission, EvictionPolicy};
+
+/// Number of independent hash functions (rows) used by the count-min
+/// sketch; the doorkeeper uses two of its own. Four rows keeps the false-positive rate on frequency
+/// estimates low without much memory overhead.
+const DEPTH: usize = 4;
+
+/// Saturating counter width: 4 bits, so estimates top out at 15.
+const COUNTER_MAX: u8 = 15;
+
+fn hash_with_seed(seed: u64, key: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// An approximate frequency counter: for each key, `depth` 4-bit
+/// saturating counters are maintained, and the estimate is the minimum of
+/// them (this bounds the error to always overestimate, never
+/// underestimate, which is the safe direction for admission decisions).
+struct CountMinSketch {
+    width: usize,
+    counters: Vec<[u8; DEPTH]>,
+}
+
+impl CountMinSketch {
+    fn new(width: usize) -> CountMinSketch {
+        let width = width.max(1);
+        CountMinSketch {
+            width,
+            counters: vec![[0; DEPTH]; width],
+        }
+    }
+
+    fn slots(&self, key: &str) -> [usize; DEPTH] {
+        let mut slots = [0usize; DEPTH];
+        for (row, slot) in slots.iter_mut().enumerate() {
+            *slot = (hash_with_seed(row as u64, key) as usize) % self.width;
+        }
+        slots
+    }
+
+    fn increment(&mut self, key: &str) {
+        for (row, slot) in self.slots(key).into_iter().enumerate() {
+            let counter = &mut self.counters[slot][row];
+            if *counter < COUNTER_MAX {
+                *counter += 1;
+            }
+        }
+    }
+
+    fn estimate(&self, key: &str) -> u8 {
+        self.slots(key)
+            .into_iter()
+            .enumerate()
+            .map(|(row, slot)| self.counters[slot][row])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Halve every counter. Run periodically so the sketch tracks recent
+    /// behavior rather than accumulating stale all-time counts.
+    fn age(&mut self) {
+        for row in &mut self.counters {
+            for counter in row.iter_mut() {
+                *counter /= 2;
+            }
+        }
+    }
+}
+
+/// A bloom filter used as TinyLFU's "doorkeeper": items are only counted
+/// in the (more expensive, lossier) count-min sketch once they've been
+/// seen at least twice, so one-off scans don't pollute the frequency
+/// estimate of genuinely hot keys.
+struct Doorkeeper {
+    bits: Vec<bool>,
+}
+
+impl Doorkeeper {
+    fn new(size: usize) -> Doorkeeper {
+        Doorkeeper {
+            bits: vec![false; size.max(1)],
+        }
+    }
+
+    fn slots(&self, key: &str) -> [usize; 2] {
+        [
+            (hash_with_seed(0, key) as usize) % self.bits.len(),
+            (hash_with_seed(1, key) as usize) % self.bits.len(),
+        ]
+    }
+
+    fn contains(&self, key: &str) -> bool {
+        self.slots(key).iter().all(|&slot| self.bits[slot])
+    }
+
+    /// Returns whether `key` was already present, and marks it present
+    /// either way.
+    fn check_and_set(&mut self, key: &str) -> bool {
+        let already_present = self.contains(key);
+        for slot in self.slots(key) {
+            self.bits[slot] = true;
+        }
+        already_present
+    }
+
+    fn clear(&mut self) {
+        self.bits.iter_mut().for_each(|bit| *bit = false);
+    }
+}
+
+/// A TinyLFU admission policy: estimates each key's access frequency with
+/// a count-min sketch guarded by a doorkeeper, and admits a new entry into
+/// a full cache only if it's estimated to be accessed more often than the
+/// least-frequently-used of the sampled eviction candidates.
+///
+/// This keeps cold, one-shot artifacts (a rare one-off build) from
+/// evicting hot, frequently-recompiled objects, which plain LRU is
+/// vulnerable to under scan-heavy workloads.
+pub struct TinyLfuPolicy {
+    sketch: CountMinSketch,
+    doorkeeper: Doorkeeper,
+    accesses_since_age: u64,
+    age_interval: u64,
+}
+
+impl TinyLfuPolicy {
+    /// Create a policy sized for roughly `expected_entries` distinct keys.
+    /// The sketch is oversized relative to the working set to keep the
+    /// collision (and thus overestimation) rate low.
+    pub fn new(expected_entries: usize) -> TinyLfuPolicy {
+        let width = expected_entries.saturating_mul(4).max(16);
+        TinyLfuPolicy {
+            sketch: CountMinSketch::new(width),
+            doorkeeper: Doorkeeper::new(width),
+            accesses_since_age: 0,
+            age_interval: expected_entries.max(16) as u64,
+        }
+    }
+
+    fn frequency(&self, key: &str) -> u8 {
+        let estimate = self.sketch.estimate(key);
+        if self.doorkeeper.contains(key) {
+            estimate.saturating_add(1)
+        } else {
+            estimate
+        }
+    }
+}
+
+impl EvictionPolicy for TinyLfuPolicy {
+    fn record_access(&mut self, key: &str) {
+        if self.doorkeeper.check_and_set(key) {
+            self.sketch.increment(key);
+        }
+
+        self.accesses_since_age += 1;
+        if self.accesses_since_age >= self.age_interval {
+            self.sketch.age();
+            self.doorkeeper.clear();
+            self.accesses_since_age = 0;
+        }
+    }
+
+    fn admit(&mut self, key: &str, candidates: &[&str]) -> Admission {
+        let incoming_frequency = self.frequency(key);
+        let victim = candidates.iter().min_by_key(|candidate| self.frequency(candidate));
+        let min_victim_frequency = victim.map(|candidate| self.frequency(candidate)).unwrap_or(0);
+
+        if incoming_frequency > min_victim_frequency {
+            Admission::Admit(victim.map(|candidate| (*candidate).to_owned()))
+        } else {
+            Admission::Reject
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doorkeeper_does_not_count_a_single_touch() {
+        let mut policy = TinyLfuPolicy::new(64);
+        policy.record_access("once");
+        // A key seen exactly once lives in the doorkeeper only; the
+        // count-min sketch hasn't been incremented for it yet.
+        assert_eq!(policy.sketch.estimate("once"), 0);
+        assert!(policy.doorkeeper.contains("once"));
+    }
+
+    #[test]
+    fn repeated_access_increases_frequency_estimate() {
+        let mut policy = TinyLfuPolicy::new(64);
+        for _ in 0..5 {
+            policy.record_access("hot");
+        }
+        policy.record_access("cold");
+
+        assert!(policy.frequency("hot") > policy.frequency("cold"));
+    }
+
+    #[test]
+    fn admits_a_hotter_key_over_a_colder_candidate() {
+        let mut policy = TinyLfuPolicy::new(64);
+        for _ in 0..10 {
+            policy.record_access("incoming");
+        }
+        policy.record_access("victim");
+
+        assert_eq!(policy.admit("incoming", &["victim"]), Admission::Admit(Some("victim".to_owned())));
+    }
+
+    #[test]
+    fn rejects_a_colder_key_than_the_candidates() {
+        let mut policy = TinyLfuPolicy::new(64);
+        policy.record_access("incoming");
+        for _ in 0..10 {
+            policy.record_access("victim");
+        }
+
+        assert_eq!(policy.admit("incoming", &["victim"]), Admission::Reject);
+    }
+
+    #[test]
+    fn admission_names_the_least_frequent_candidate_as_victim() {
+        let mut policy = TinyLfuPolicy::new(64);
+        for _ in 0..10 {
+            policy.record_access("incoming");
+        }
+        for _ in 0..5 {
+            policy.record_access("warm");
+        }
+        policy.record_access("cold");
+
+        assert_eq!(policy.admit("incoming", &["warm", "cold"]), Admission::Admit(Some("cold".to_owned())));
+    }
+
+    #[test]
+    fn aging_halves_counters() {
+        let mut sketch = CountMinSketch::new(16);
+        for _ in 0..4 {
+            sketch.increment("key");
+        }
+        assert_eq!(sketch.estimate("key"), 4);
+        sketch.age();
+        assert_eq!(sketch.estimate("key"), 2);
+    }
+
+    #[test]
+    fn counters_saturate_rather_than_overflow() {
+        let mut sketch = CountMinSketch::new(16);
+        for _ in 0..(COUNTER_MAX as u32 + 10) {
+            sketch.increment("key");
+        }
+        assert_eq!(sketch.estimate("key"), COUNTER_MAX);
+    }
+
+    #[test]
+    fn unrelated_keys_do_not_share_an_estimate() {
+        let mut sketch = CountMinSketch::new(1024);
+        sketch.increment("a");
+        assert_eq!(sketch.estimate("b"), 0);
+    }
+}
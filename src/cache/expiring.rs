@@ -0,0 +1,236 @@
+// Copyright 2016 Mozilla Foundation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+
+use crate::cache::{Cache, CacheMode, CacheWrite, Storage};
+use crate::errors::*;
+
+/// Bookkeeping kept alongside an entry so `ExpiringStorage` can decide
+/// whether it's still live: when it was written, and when it was last
+/// read.
+#[derive(Clone, Copy)]
+struct EntryMetadata {
+    written_at: SystemTime,
+    last_accessed_at: SystemTime,
+}
+
+/// A `Storage` decorator that enforces an optional time-to-live (max age
+/// since the entry was written) and time-to-idle (max age since the entry
+/// was last read) on top of the wrapped backend.
+///
+/// Metadata is tracked in-process, keyed by cache key; it does not
+/// survive a restart, so after a restart entries are treated as freshly
+/// written until touched again. On `get`, an entry past its TTL or TTI is
+/// reported as a `Cache::Miss`, and keeps being reported as one until the
+/// key is written again; otherwise its last-access time is refreshed.
+///
+/// **This does not reclaim storage in the wrapped backend.** `Storage`
+/// has no delete operation, so an expired entry's bytes are left in
+/// `inner` indefinitely, until (if ever) the same key is written again
+/// and overwrites them. `ExpiringStorage` therefore stops expired entries
+/// from being served as hits, but on its own it does not bound backend
+/// growth the way a reaper would; pair it with a backend that ages out
+/// its own storage (e.g. bucket lifecycle rules) if that matters.
+pub struct ExpiringStorage {
+    inner: Arc<dyn Storage>,
+    ttl: Option<Duration>,
+    tti: Option<Duration>,
+    metadata: Mutex<std::collections::HashMap<String, EntryMetadata>>,
+}
+
+impl ExpiringStorage {
+    /// Wrap `inner`, expiring entries older than `ttl` since they were
+    /// written, or idle for longer than `tti` since they were last read.
+    /// Either bound may be `None` to disable it.
+    pub fn new(inner: Arc<dyn Storage>, ttl: Option<Duration>, tti: Option<Duration>) -> ExpiringStorage {
+        ExpiringStorage {
+            inner,
+            ttl,
+            tti,
+            metadata: Mutex::new(std::collections::HashMap::new()),
+        }
+    }
+
+    async fn is_expired(&self, key: &str, now: SystemTime) -> bool {
+        let metadata = self.metadata.lock().await;
+        let entry = match metadata.get(key) {
+            Some(entry) => *entry,
+            // No metadata means we've never seen this key in this
+            // process; treat it as fresh rather than expired.
+            None => return false,
+        };
+
+        if let Some(ttl) = self.ttl {
+            if now.duration_since(entry.written_at).unwrap_or_default() > ttl {
+                return true;
+            }
+        }
+        if let Some(tti) = self.tti {
+            if now.duration_since(entry.last_accessed_at).unwrap_or_default() > tti {
+                return true;
+            }
+        }
+        false
+    }
+
+    async fn touch(&self, key: &str, now: SystemTime) {
+        let mut metadata = self.metadata.lock().await;
+        let entry = metadata.entry(key.to_owned()).or_insert(EntryMetadata {
+            written_at: now,
+            last_accessed_at: now,
+        });
+        entry.last_accessed_at = now;
+    }
+
+    async fn record_write(&self, key: &str, now: SystemTime) {
+        let mut metadata = self.metadata.lock().await;
+        metadata.insert(
+            key.to_owned(),
+            EntryMetadata {
+                written_at: now,
+                last_accessed_at: now,
+            },
+        );
+    }
+}
+
+#[async_trait]
+impl Storage for ExpiringStorage {
+    async fn get(&self, key: &str) -> Result<Cache> {
+        let now = SystemTime::now();
+        if self.is_expired(key, now).await {
+            // `Storage` has no delete operation, so the stale entry stays
+            // in the backend. Its metadata is kept as it is, so the entry
+            // is never looked at again (every later `get` finds it expired)
+            // until a future `put` overwrites it and resets the metadata.
+            return Ok(Cache::Miss);
+        }
+
+        match self.inner.get(key).await? {
+            Cache::Hit(entry) => {
+                self.touch(key, now).await;
+                Ok(Cache::Hit(entry))
+            }
+            Cache::Miss => Ok(Cache::Miss),
+        }
+    }
+
+    async fn put(&self, key: &str, entry: CacheWrite) -> Result<Duration> {
+        let now = SystemTime::now();
+        let duration = self.inner.put(key, entry).await?;
+        self.record_write(key, now).await;
+        Ok(duration)
+    }
+
+    async fn check(&self) -> Result<CacheMode> {
+        self.inner.check().await
+    }
+
+    fn location(&self) -> String {
+        format!("Expiring {{ {} }}", self.inner.location())
+    }
+
+    async fn current_size(&self) -> Result<Option<u64>> {
+        self.inner.current_size().await
+    }
+
+    async fn max_size(&self) -> Result<Option<u64>> {
+        self.inner.max_size().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::test_helpers::MemoryStorage;
+
+    #[tokio::test]
+    async fn fresh_entry_is_a_hit() {
+        let storage = ExpiringStorage::new(
+            Arc::new(MemoryStorage::new()),
+            Some(Duration::from_secs(60)),
+            Some(Duration::from_secs(60)),
+        );
+        storage.put("key", CacheWrite::from_bytes(b"data".to_vec())).await.unwrap();
+
+        assert!(matches!(storage.get("key").await.unwrap(), Cache::Hit(_)));
+    }
+
+    #[tokio::test]
+    async fn entry_past_its_ttl_is_a_miss() {
+        let storage = ExpiringStorage::new(Arc::new(MemoryStorage::new()), Some(Duration::from_secs(60)), None);
+        storage.put("key", CacheWrite::from_bytes(b"data".to_vec())).await.unwrap();
+
+        // Pretend the entry was written long enough ago to exceed the TTL.
+        {
+            let mut metadata = storage.metadata.lock().await;
+            let entry = metadata.get_mut("key").unwrap();
+            entry.written_at = entry.written_at - Duration::from_secs(120);
+        }
+
+        assert!(matches!(storage.get("key").await.unwrap(), Cache::Miss));
+    }
+
+    #[tokio::test]
+    async fn entry_past_its_tti_is_a_miss() {
+        let storage = ExpiringStorage::new(Arc::new(MemoryStorage::new()), None, Some(Duration::from_secs(60)));
+        storage.put("key", CacheWrite::from_bytes(b"data".to_vec())).await.unwrap();
+
+        {
+            let mut metadata = storage.metadata.lock().await;
+            let entry = metadata.get_mut("key").unwrap();
+            entry.last_accessed_at = entry.last_accessed_at - Duration::from_secs(120);
+        }
+
+        assert!(matches!(storage.get("key").await.unwrap(), Cache::Miss));
+    }
+
+    #[tokio::test]
+    async fn get_refreshes_last_accessed_time() {
+        let storage = ExpiringStorage::new(Arc::new(MemoryStorage::new()), None, Some(Duration::from_secs(60)));
+        storage.put("key", CacheWrite::from_bytes(b"data".to_vec())).await.unwrap();
+
+        {
+            let mut metadata = storage.metadata.lock().await;
+            let entry = metadata.get_mut("key").unwrap();
+            entry.last_accessed_at = entry.last_accessed_at - Duration::from_secs(30);
+        }
+
+        // Still within the 60s TTI, so this read succeeds and refreshes
+        // last_accessed_at back to roughly now.
+        assert!(matches!(storage.get("key").await.unwrap(), Cache::Hit(_)));
+        let last_accessed_at = storage.metadata.lock().await.get("key").unwrap().last_accessed_at;
+        assert!(SystemTime::now().duration_since(last_accessed_at).unwrap() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn unbounded_entry_never_expires() {
+        let storage = ExpiringStorage::new(Arc::new(MemoryStorage::new()), None, None);
+        storage.put("key", CacheWrite::from_bytes(b"data".to_vec())).await.unwrap();
+
+        {
+            let mut metadata = storage.metadata.lock().await;
+            let entry = metadata.get_mut("key").unwrap();
+            entry.written_at = std::time::UNIX_EPOCH;
+            entry.last_accessed_at = std::time::UNIX_EPOCH;
+        }
+
+        assert!(matches!(storage.get("key").await.unwrap(), Cache::Hit(_)));
+    }
+}
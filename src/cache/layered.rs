@@ -0,0 +1,199 @@
+// Copyright 2016 Mozilla Foundation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use log::warn;
+
+use crate::cache::{Cache, CacheMode, CacheRead, CacheWrite, Storage};
+use crate::errors::*;
+
+/// A `Storage` that combines a single read-write primary backend with an
+/// ordered list of read-only (or read-write, but unused for writes)
+/// fallback backends.
+///
+/// `get` is tried against the primary first, then each fallback in turn,
+/// returning the first hit. A hit on a fallback is optionally "promoted"
+/// into the primary so that later lookups for the same key are served
+/// locally. `put` always targets the primary; fallbacks are never written
+/// to directly.
+pub struct LayeredStorage {
+    primary: Arc<dyn Storage>,
+    fallbacks: Vec<Arc<dyn Storage>>,
+    promote_on_read: bool,
+}
+
+impl LayeredStorage {
+    /// Create a new `LayeredStorage` that writes to `primary` and falls
+    /// back to `fallbacks` (tried in order) on a `get` miss.
+    ///
+    /// If `promote_on_read` is set, entries found in a fallback are
+    /// re-`put` into `primary` so subsequent lookups avoid the fallback
+    /// tiers entirely.
+    pub fn new(
+        primary: Arc<dyn Storage>,
+        fallbacks: Vec<Arc<dyn Storage>>,
+        promote_on_read: bool,
+    ) -> LayeredStorage {
+        LayeredStorage {
+            primary,
+            fallbacks,
+            promote_on_read,
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for LayeredStorage {
+    async fn get(&self, key: &str) -> Result<Cache> {
+        if let Cache::Hit(entry) = self.primary.get(key).await? {
+            return Ok(Cache::Hit(entry));
+        }
+
+        for fallback in &self.fallbacks {
+            let entry = match fallback.get(key).await? {
+                Cache::Hit(entry) => entry,
+                Cache::Miss => continue,
+            };
+
+            if !self.promote_on_read {
+                return Ok(Cache::Hit(entry));
+            }
+
+            let bytes = entry.into_bytes();
+            if let Err(e) = self.primary.put(key, CacheWrite::from_bytes(bytes.clone())).await {
+                warn!("Failed to promote cache entry for `{}` to primary: {}", key, e);
+            }
+            return Ok(Cache::Hit(CacheRead::from_bytes(bytes)));
+        }
+
+        Ok(Cache::Miss)
+    }
+
+    /// Put `entry` in the cache under `key`.
+    ///
+    /// Only the primary backend is ever written to; fallbacks are
+    /// consulted on read only.
+    async fn put(&self, key: &str, entry: CacheWrite) -> Result<Duration> {
+        self.primary.put(key, entry).await
+    }
+
+    async fn check(&self) -> Result<CacheMode> {
+        self.primary.check().await
+    }
+
+    fn location(&self) -> String {
+        let fallback_locations: Vec<_> = self.fallbacks.iter().map(|s| s.location()).collect();
+        format!(
+            "Layered {{ primary: {}, fallbacks: [{}] }}",
+            self.primary.location(),
+            fallback_locations.join(", ")
+        )
+    }
+
+    async fn current_size(&self) -> Result<Option<u64>> {
+        sum_sizes(std::iter::once(&self.primary).chain(self.fallbacks.iter()), |s| {
+            s.current_size()
+        })
+        .await
+    }
+
+    async fn max_size(&self) -> Result<Option<u64>> {
+        sum_sizes(std::iter::once(&self.primary).chain(self.fallbacks.iter()), |s| {
+            s.max_size()
+        })
+        .await
+    }
+}
+
+/// Sum an `Option<u64>`-returning size accessor across every tier,
+/// treating tiers that don't report a size as contributing nothing.
+/// Returns `None` if no tier reports a size.
+async fn sum_sizes<'a, I, F, Fut>(tiers: I, f: F) -> Result<Option<u64>>
+where
+    I: Iterator<Item = &'a Arc<dyn Storage>>,
+    F: Fn(&'a Arc<dyn Storage>) -> Fut,
+    Fut: std::future::Future<Output = Result<Option<u64>>>,
+{
+    let mut total: Option<u64> = None;
+    for tier in tiers {
+        if let Some(size) = f(tier).await? {
+            total = Some(total.unwrap_or(0) + size);
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::test_helpers::MemoryStorage;
+
+    #[tokio::test]
+    async fn hits_primary_before_fallback() {
+        let primary = Arc::new(MemoryStorage::new().seed("key", b"primary"));
+        let fallback = Arc::new(MemoryStorage::new().seed("key", b"fallback"));
+        let storage = LayeredStorage::new(primary, vec![fallback], false);
+
+        let Cache::Hit(entry) = storage.get("key").await.unwrap() else {
+            panic!("expected a hit");
+        };
+        assert_eq!(entry.into_bytes(), b"primary");
+    }
+
+    #[tokio::test]
+    async fn falls_back_and_promotes_on_read() {
+        let primary = Arc::new(MemoryStorage::new());
+        let fallback = Arc::new(MemoryStorage::new().seed("key", b"fallback"));
+        let storage = LayeredStorage::new(primary.clone(), vec![fallback], true);
+
+        let Cache::Hit(entry) = storage.get("key").await.unwrap() else {
+            panic!("expected a hit");
+        };
+        assert_eq!(entry.into_bytes(), b"fallback");
+        assert_eq!(primary.get_raw("key"), Some(b"fallback".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn fallback_without_promotion_does_not_write_primary() {
+        let primary = Arc::new(MemoryStorage::new());
+        let fallback = Arc::new(MemoryStorage::new().seed("key", b"fallback"));
+        let storage = LayeredStorage::new(primary.clone(), vec![fallback], false);
+
+        storage.get("key").await.unwrap();
+        assert!(!primary.contains("key"));
+    }
+
+    #[tokio::test]
+    async fn put_only_targets_primary() {
+        let primary = Arc::new(MemoryStorage::new());
+        let fallback = Arc::new(MemoryStorage::new());
+        let storage = LayeredStorage::new(primary.clone(), vec![fallback.clone()], false);
+
+        storage.put("key", CacheWrite::from_bytes(b"data".to_vec())).await.unwrap();
+        assert!(primary.contains("key"));
+        assert!(!fallback.contains("key"));
+    }
+
+    #[tokio::test]
+    async fn miss_when_absent_everywhere() {
+        let primary = Arc::new(MemoryStorage::new());
+        let fallback = Arc::new(MemoryStorage::new());
+        let storage = LayeredStorage::new(primary, vec![fallback], true);
+
+        assert!(matches!(storage.get("missing").await.unwrap(), Cache::Miss));
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright 2016 Mozilla Foundation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod disk;
+pub mod eviction;
+pub mod expiring;
+pub mod layered;
+pub mod readonly;
+#[cfg(test)]
+pub(crate) mod test_helpers;
+pub mod verifying;
+
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::errors::*;
+
+/// The result of a cache lookup.
+pub enum Cache {
+    /// The entry was found in the cache.
+    Hit(CacheRead),
+    /// The entry was not found in the cache.
+    Miss,
+}
+
+/// The mode a `Storage` backend is currently usable in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheMode {
+    /// The cache can only be used to `get`.
+    ReadOnly,
+    /// The cache can be used for both `get` and `put`.
+    ReadWrite,
+}
+
+/// Data retrieved from a `Storage::get` cache hit.
+pub struct CacheRead {
+    data: Vec<u8>,
+}
+
+impl CacheRead {
+    /// Wrap raw bytes read back from a cache backend.
+    pub fn from_bytes(data: Vec<u8>) -> CacheRead {
+        CacheRead { data }
+    }
+
+    /// Consume this `CacheRead`, returning the underlying bytes.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// The size in bytes of the cached entry.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Whether the cached entry is empty.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
+
+/// Data to be written to a cache backend via `Storage::put`.
+pub struct CacheWrite {
+    data: Vec<u8>,
+}
+
+impl CacheWrite {
+    /// Build a `CacheWrite` from raw bytes.
+    pub fn from_bytes(data: Vec<u8>) -> CacheWrite {
+        CacheWrite { data }
+    }
+
+    /// Borrow the bytes that will be written to the cache.
+    pub fn bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Consume this `CacheWrite`, returning the underlying bytes.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+/// A storage backend for the compiler cache.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Get the cache entry for `key`, if it exists.
+    async fn get(&self, key: &str) -> Result<Cache>;
+
+    /// Put `entry` in the cache under `key`.
+    ///
+    /// Returns a `Future` that will provide the result or error when the put is
+    /// finished. A size-bounded backend may reject the entry outright (for
+    /// example, an eviction policy judging it less valuable than what's
+    /// already cached); callers should not assume a `put` that returns
+    /// `Err` left the cache in a state where `key` is retrievable.
+    async fn put(&self, key: &str, entry: CacheWrite) -> Result<Duration>;
+
+    /// Check the cache capability.
+    ///
+    /// - `Ok(CacheMode::ReadOnly)` means cache can only be used to `get`
+    ///   cache.
+    /// - `Ok(CacheMode::ReadWrite)` means cache can do both `get` and `put`.
+    /// - `Err(err)` means cache is not setup correctly or not match with
+    ///   users input (for example, user try to use `ReadWrite` but cache
+    ///   is `ReadOnly`).
+    ///
+    /// We will provide a default implementation which returns
+    /// `Ok(CacheMode::ReadWrite)` for service that doesn't
+    /// support check yet.
+    async fn check(&self) -> Result<CacheMode> {
+        Ok(CacheMode::ReadWrite)
+    }
+
+    /// Get the storage location.
+    fn location(&self) -> String;
+
+    /// Get the current storage usage, if applicable.
+    async fn current_size(&self) -> Result<Option<u64>>;
+
+    /// Get the maximum storage size, if applicable.
+    async fn max_size(&self) -> Result<Option<u64>>;
+}
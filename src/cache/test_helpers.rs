@@ -0,0 +1,109 @@
+// Copyright 2016 Mozilla Foundation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test-only `Storage` implementations shared by the `cache` decorator
+//! unit tests.
+
+#![cfg(test)]
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use crate::cache::{Cache, CacheMode, CacheRead, CacheWrite, Storage};
+use crate::errors::*;
+
+/// An in-memory `Storage` backed by a `HashMap`, for exercising decorators
+/// without touching the filesystem or a real remote backend.
+pub(crate) struct MemoryStorage {
+    entries: Mutex<HashMap<String, Vec<u8>>>,
+    mode: CacheMode,
+}
+
+impl MemoryStorage {
+    pub(crate) fn new() -> MemoryStorage {
+        MemoryStorage {
+            entries: Mutex::new(HashMap::new()),
+            mode: CacheMode::ReadWrite,
+        }
+    }
+
+    pub(crate) fn read_only() -> MemoryStorage {
+        MemoryStorage {
+            entries: Mutex::new(HashMap::new()),
+            mode: CacheMode::ReadOnly,
+        }
+    }
+
+    pub(crate) fn seed(self, key: &str, value: &[u8]) -> MemoryStorage {
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_owned(), value.to_owned());
+        self
+    }
+
+    pub(crate) fn contains(&self, key: &str) -> bool {
+        self.entries.lock().unwrap().contains_key(key)
+    }
+
+    pub(crate) fn get_raw(&self, key: &str) -> Option<Vec<u8>> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    async fn get(&self, key: &str) -> Result<Cache> {
+        match self.entries.lock().unwrap().get(key) {
+            Some(data) => Ok(Cache::Hit(CacheRead::from_bytes(data.clone()))),
+            None => Ok(Cache::Miss),
+        }
+    }
+
+    async fn put(&self, key: &str, entry: CacheWrite) -> Result<Duration> {
+        if self.mode == CacheMode::ReadOnly {
+            error_chain::bail!("storage is read-only");
+        }
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_owned(), entry.into_bytes());
+        Ok(Duration::from_secs(0))
+    }
+
+    async fn check(&self) -> Result<CacheMode> {
+        Ok(self.mode)
+    }
+
+    fn location(&self) -> String {
+        "memory".to_owned()
+    }
+
+    async fn current_size(&self) -> Result<Option<u64>> {
+        Ok(Some(
+            self.entries.lock().unwrap().values().map(|v| v.len() as u64).sum(),
+        ))
+    }
+
+    async fn max_size(&self) -> Result<Option<u64>> {
+        Ok(None)
+    }
+}
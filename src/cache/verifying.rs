@@ -0,0 +1,209 @@
+// Copyright 2016 Mozilla Foundation
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use base64::Engine;
+use log::warn;
+use sha2::{Digest, Sha256};
+
+use crate::cache::{Cache, CacheMode, CacheRead, CacheWrite, Storage};
+use crate::errors::*;
+
+/// Prefix under which content-addressed blobs are stored, separate from
+/// the namespace used for the compiler cache key -> content address
+/// mapping.
+const BLOB_PREFIX: &str = "blobs/";
+
+/// Compute the Subresource-Integrity-style content address for `data`,
+/// e.g. `sha256-<base64>`.
+///
+/// Encoded with the URL-safe, unpadded base64 alphabet rather than the
+/// standard one: the address is used as a path component (see
+/// `blob_key`), and the standard alphabet's `/` and `+` would otherwise
+/// turn most addresses into multi-segment paths or invalid filenames.
+fn content_address(data: &[u8]) -> String {
+    let digest = Sha256::digest(data);
+    format!(
+        "sha256-{}",
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(digest)
+    )
+}
+
+/// A `Storage` decorator that makes the wrapped backend content-addressable
+/// and self-verifying.
+///
+/// On `put`, the entry's bytes are hashed with SHA-256 and stored under a
+/// content address derived from that hash; the compiler cache `key` is
+/// then mapped to that address. Because the address is a pure function of
+/// the bytes, two keys whose outputs are byte-identical end up pointing at
+/// the same stored blob, so storage is deduplicated automatically.
+///
+/// On `get`, the fetched bytes are re-hashed and compared against the
+/// address they were stored under. A mismatch (corruption or tampering in
+/// the underlying backend) is reported as a `Cache::Miss` rather than
+/// handed back to the compiler.
+pub struct VerifyingStorage {
+    inner: Arc<dyn Storage>,
+}
+
+impl VerifyingStorage {
+    /// Wrap `inner` to make it content-addressable and self-verifying.
+    pub fn new(inner: Arc<dyn Storage>) -> VerifyingStorage {
+        VerifyingStorage { inner }
+    }
+
+    fn blob_key(address: &str) -> String {
+        format!("{}{}", BLOB_PREFIX, address)
+    }
+}
+
+#[async_trait]
+impl Storage for VerifyingStorage {
+    async fn get(&self, key: &str) -> Result<Cache> {
+        let address = match self.inner.get(key).await? {
+            Cache::Hit(entry) => match String::from_utf8(entry.into_bytes()) {
+                Ok(address) => address,
+                Err(_) => {
+                    warn!(
+                        "Cache entry for `{}` has a corrupt content address mapping, treating as a miss",
+                        key
+                    );
+                    return Ok(Cache::Miss);
+                }
+            },
+            Cache::Miss => return Ok(Cache::Miss),
+        };
+
+        let blob = match self.inner.get(&Self::blob_key(&address)).await? {
+            Cache::Hit(entry) => entry,
+            Cache::Miss => {
+                warn!(
+                    "Cache entry for `{}` references missing blob `{}`",
+                    key, address
+                );
+                return Ok(Cache::Miss);
+            }
+        };
+
+        let bytes = blob.into_bytes();
+        if content_address(&bytes) != address {
+            warn!(
+                "Cache entry for `{}` failed integrity verification, treating as a miss",
+                key
+            );
+            return Ok(Cache::Miss);
+        }
+
+        Ok(Cache::Hit(CacheRead::from_bytes(bytes)))
+    }
+
+    async fn put(&self, key: &str, entry: CacheWrite) -> Result<Duration> {
+        let bytes = entry.into_bytes();
+        let address = content_address(&bytes);
+
+        // The blob is content-addressed, so re-putting an address that's
+        // already present is a harmless no-op and naturally deduplicates
+        // identical outputs produced under different keys.
+        let duration = self
+            .inner
+            .put(&Self::blob_key(&address), CacheWrite::from_bytes(bytes))
+            .await?;
+        self.inner
+            .put(key, CacheWrite::from_bytes(address.into_bytes()))
+            .await?;
+        Ok(duration)
+    }
+
+    async fn check(&self) -> Result<CacheMode> {
+        self.inner.check().await
+    }
+
+    fn location(&self) -> String {
+        format!("Verifying {{ {} }}", self.inner.location())
+    }
+
+    async fn current_size(&self) -> Result<Option<u64>> {
+        self.inner.current_size().await
+    }
+
+    async fn max_size(&self) -> Result<Option<u64>> {
+        self.inner.max_size().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::test_helpers::MemoryStorage;
+
+    #[tokio::test]
+    async fn put_then_get_round_trips() {
+        let storage = VerifyingStorage::new(Arc::new(MemoryStorage::new()));
+        storage.put("key", CacheWrite::from_bytes(b"hello".to_vec())).await.unwrap();
+
+        let Cache::Hit(entry) = storage.get("key").await.unwrap() else {
+            panic!("expected a hit");
+        };
+        assert_eq!(entry.into_bytes(), b"hello");
+    }
+
+    #[tokio::test]
+    async fn identical_outputs_under_different_keys_share_one_blob() {
+        let inner = Arc::new(MemoryStorage::new());
+        let storage = VerifyingStorage::new(inner.clone());
+        storage.put("key-a", CacheWrite::from_bytes(b"same bytes".to_vec())).await.unwrap();
+        storage.put("key-b", CacheWrite::from_bytes(b"same bytes".to_vec())).await.unwrap();
+
+        // Two key -> address mappings, plus exactly one deduplicated blob.
+        assert_eq!(inner.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn corrupted_blob_is_reported_as_a_miss() {
+        let inner = Arc::new(MemoryStorage::new());
+        let storage = VerifyingStorage::new(inner.clone());
+        storage.put("key", CacheWrite::from_bytes(b"hello".to_vec())).await.unwrap();
+
+        let address = String::from_utf8(inner.get_raw("key").unwrap()).unwrap();
+        let blob_key = VerifyingStorage::blob_key(&address);
+        inner.put(&blob_key, CacheWrite::from_bytes(b"tampered".to_vec())).await.unwrap();
+
+        assert!(matches!(storage.get("key").await.unwrap(), Cache::Miss));
+    }
+
+    #[tokio::test]
+    async fn corrupt_address_mapping_is_reported_as_a_miss_not_an_error() {
+        let inner = Arc::new(MemoryStorage::new());
+        let storage = VerifyingStorage::new(inner.clone());
+        // Not valid UTF-8.
+        inner.put("key", CacheWrite::from_bytes(vec![0xff, 0xfe])).await.unwrap();
+
+        assert!(matches!(storage.get("key").await.unwrap(), Cache::Miss));
+    }
+
+    #[tokio::test]
+    async fn missing_blob_for_a_known_address_is_a_miss() {
+        let inner = Arc::new(MemoryStorage::new());
+        let storage = VerifyingStorage::new(inner.clone());
+        inner
+            .put("key", CacheWrite::from_bytes(content_address(b"hello").into_bytes()))
+            .await
+            .unwrap();
+
+        assert!(matches!(storage.get("key").await.unwrap(), Cache::Miss));
+    }
+}